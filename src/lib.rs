@@ -1,12 +1,15 @@
 use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Parser, Tag, TagEnd};
 use run::{TestStatus, run_tests};
+use std::collections::HashMap;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{Error as IoError, Read};
 use std::mem;
 use std::path::{Path, PathBuf};
 mod run;
+pub mod rt;
 
+#[derive(Clone)]
 struct Config {
     root_dir: PathBuf,
     test_dir: PathBuf,
@@ -70,6 +73,157 @@ pub fn test_snippets_in_files(
     run_tests(&config, tests)
 }
 
+struct CodegenConfig {
+    out_file: PathBuf,
+    target_triple: String,
+    docs: Vec<String>,
+    crate_name: String,
+    edition: String,
+}
+
+/// Extracts every doc in `docs` and writes a generated test file with one
+/// `#[test] fn` per snippet, to be pulled into the crate-under-test's test
+/// binary via `include!(concat!(env!("OUT_DIR"), "/skeptic-tests.rs"))`.
+///
+/// Meant to be called from a `build.rs`: reads `OUT_DIR`, `CARGO_MANIFEST_DIR`
+/// and `TARGET` from the environment the way cargo sets them for build
+/// scripts.
+pub fn generate_doc_tests<T>(docs: &[T])
+where
+    T: AsRef<Path>,
+{
+    if docs.is_empty() {
+        return;
+    }
+
+    let docs = docs
+        .iter()
+        .map(|path| path.as_ref().to_str().unwrap().to_owned())
+        .filter(|d| !is_template_file(Path::new(d)))
+        .collect::<Vec<_>>();
+
+    // Inform cargo that it needs to rerun the build script if one of the skeptic files are
+    // modified
+    for doc in &docs {
+        println!("cargo:rerun-if-changed={}", doc);
+
+        let skt = format!("{}.skt.md", doc);
+        if Path::new(&skt).exists() {
+            println!("cargo:rerun-if-changed={}", skt);
+        }
+    }
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let cargo_manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    let mut out_file = PathBuf::from(&out_dir);
+    out_file.push("skeptic-tests.rs");
+
+    let (crate_name, edition) =
+        read_package_metadata(&Path::new(&cargo_manifest_dir).join("Cargo.toml"));
+
+    let config = CodegenConfig {
+        out_file,
+        target_triple: env::var("TARGET").expect("could not get target triple"),
+        docs,
+        crate_name,
+        edition,
+    };
+
+    write_generated_tests(&config);
+}
+
+fn write_generated_tests(config: &CodegenConfig) {
+    let tests: Vec<Test> = config
+        .docs
+        .iter()
+        .flat_map(|doc| extract_tests_from_file(Path::new(doc)).unwrap_or_default())
+        .collect();
+
+    let mut out = String::new();
+    out.push_str("// Generated by skeptic's generate_doc_tests. Do not edit.\n\n");
+
+    for test in &tests {
+        if test.ignore {
+            out.push_str("#[ignore]\n");
+        }
+
+        out.push_str(&format!(
+            "#[test]\nfn {name}() {{\n    skeptic::rt::run_test(\n        env!(\"OUT_DIR\"),\n        {target:?},\n        {name:?},\n        {text:?},\n        {no_run},\n        {should_panic},\n        {edition:?},\n        {crate_name:?},\n        &{compile_flags:?},\n    );\n}}\n\n",
+            name = sanitize_identifier(&test.name()),
+            target = config.target_triple,
+            text = test.text.join("\n"),
+            no_run = test.no_run,
+            should_panic = test.should_panic,
+            edition = test.edition.as_deref().unwrap_or(&config.edition),
+            crate_name = config.crate_name,
+            compile_flags = test.compile_flags,
+        ));
+    }
+
+    fs::write(&config.out_file, out).expect("failed to write skeptic-tests.rs");
+}
+
+/// Reads the `[package]` `name` (rewritten with `_` for `-`, so it's a valid
+/// `extern crate` identifier) and `edition` out of `cargo_toml_path`,
+/// defaulting the edition to `"2021"` if the manifest doesn't set one.
+fn read_package_metadata(cargo_toml_path: &Path) -> (String, String) {
+    let contents = fs::read_to_string(cargo_toml_path).unwrap_or_default();
+    let mut in_package = false;
+    let mut name = String::from("the_crate_under_test");
+    let mut edition = String::from("2021");
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_package = trimmed == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+
+        for (key, slot) in [("name", &mut name), ("edition", &mut edition)] {
+            if let Some(rest) = trimmed.strip_prefix(key) {
+                let Some(rest) = rest.trim_start().strip_prefix('=') else {
+                    continue;
+                };
+                let rest = rest.trim_start();
+                let Some(rest) = rest.strip_prefix('"') else {
+                    continue;
+                };
+                if let Some(end) = rest.find('"') {
+                    *slot = rest[..end].to_owned();
+                }
+            }
+        }
+    }
+
+    (name.replace('-', "_"), edition)
+}
+
+/// Turns `name` into a valid Rust identifier for a generated `#[test] fn`,
+/// collapsing runs of characters a section heading could contain (spaces,
+/// punctuation) into a single `_`, and prefixing a `_` if the result would
+/// start with a digit.
+fn sanitize_identifier(name: &str) -> String {
+    let ident = name
+        .to_ascii_lowercase()
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+
+    if ident.starts_with(|ch: char| ch.is_ascii_digit()) {
+        format!("_{}", ident)
+    } else {
+        ident
+    }
+}
+
 pub fn markdown_files_of_directory(dir: &str) -> Vec<PathBuf> {
     use glob::{MatchOptions, glob_with};
 
@@ -83,6 +237,9 @@ pub fn markdown_files_of_directory(dir: &str) -> Vec<PathBuf> {
     for path in glob_with(&format!("{}/**/*.md", dir), opts)
         .expect("Failed to read glob pattern")
         .filter_map(Result::ok)
+        // `**/*.md` also matches `foo.md.skt.md`; those are template files,
+        // not standalone docs, and must not be extracted as tests themselves.
+        .filter(|path| !is_template_file(path))
     {
         out.push(path.to_str().unwrap().into());
     }
@@ -107,6 +264,10 @@ struct Test {
     ignore: bool,
     no_run: bool,
     should_panic: bool,
+    template: Option<String>,
+    edition: Option<String>,
+    compile_flags: Vec<String>,
+    no_crate_inject: bool,
 }
 
 impl Test {
@@ -190,6 +351,10 @@ fn extract_tests_from_string(s: &str, file_stem: &str) -> Vec<Test> {
                         ignore: info.ignore,
                         no_run: info.no_run,
                         should_panic: info.should_panic,
+                        template: info.template,
+                        edition: info.edition,
+                        compile_flags: info.compile_flags,
+                        no_crate_inject: info.no_crate_inject,
                     });
                 }
             }
@@ -204,9 +369,15 @@ struct CodeBlockInfo {
     should_panic: bool,
     ignore: bool,
     no_run: bool,
+    template: Option<String>,
+    edition: Option<String>,
+    compile_flags: Vec<String>,
+    no_crate_inject: bool,
 }
 
 fn parse_code_block_info(info: &str) -> CodeBlockInfo {
+    let template = parse_template_attr(info);
+    let compile_flags = parse_compile_flags_attr(info);
     let tokens = info.split(|c: char| !(c == '_' || c == '-' || c.is_alphanumeric()));
 
     let mut seen_rust_tags = false;
@@ -216,6 +387,10 @@ fn parse_code_block_info(info: &str) -> CodeBlockInfo {
         should_panic: false,
         ignore: false,
         no_run: false,
+        template,
+        edition: None,
+        compile_flags,
+        no_crate_inject: false,
     };
 
     for token in tokens {
@@ -237,6 +412,14 @@ fn parse_code_block_info(info: &str) -> CodeBlockInfo {
                 info.no_run = true;
                 seen_rust_tags = true;
             }
+            "edition2015" | "edition2018" | "edition2021" | "edition2024" => {
+                info.edition = Some(token["edition".len()..].to_owned());
+                seen_rust_tags = true;
+            }
+            "no_crate_inject" => {
+                info.no_crate_inject = true;
+                seen_rust_tags = true;
+            }
             _ => seen_other_tags = true,
         }
     }
@@ -246,6 +429,101 @@ fn parse_code_block_info(info: &str) -> CodeBlockInfo {
     info
 }
 
+/// Pulls the flags out of a `compile-flags:...` directive in a fence info string.
+///
+/// Like `template="..."`, this can't be handled by the plain token splitter
+/// since it needs to keep whitespace between individual flags; the directive
+/// is expected to run to the end of the info string.
+fn parse_compile_flags_attr(info: &str) -> Vec<String> {
+    match info.find("compile-flags:") {
+        Some(start) => info[start + "compile-flags:".len()..]
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Pulls the value out of a `template="name"` attribute in a fence info string.
+///
+/// The normal token splitter treats `=` and `"` as separators, so a quoted
+/// attribute like this one has to be parsed out of the raw string first.
+fn parse_template_attr(info: &str) -> Option<String> {
+    let rest = &info[info.find("template")?..];
+    let rest = rest["template".len()..].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let rest = rest.strip_prefix('"')?;
+    let end = rest.find('"')?;
+    Some(rest[..end].to_owned())
+}
+
+/// Loads the named templates out of `path`'s companion `.skt.md` file, if any.
+///
+/// Each template is a fenced rust code block preceded by a heading that
+/// names it; the block's text is expected to contain a `{}` (or numbered
+/// `{0}`, `{1}`, ...) placeholder that the snippet gets substituted into.
+fn load_templates(path: &Path) -> HashMap<String, String> {
+    let mut templates = HashMap::new();
+
+    let Ok(mut file) = File::open(path) else {
+        return templates;
+    };
+    let mut s = String::new();
+    if file.read_to_string(&mut s).is_err() {
+        return templates;
+    }
+
+    let mut buffer = Buffer::None;
+    let mut name: Option<String> = None;
+
+    for event in Parser::new(&s) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) if level < HeadingLevel::H3 => {
+                buffer = Buffer::Heading(String::new());
+            }
+            Event::End(TagEnd::Heading(level)) if level < HeadingLevel::H3 => {
+                if let Buffer::Heading(heading) = mem::replace(&mut buffer, Buffer::None) {
+                    name = Some(heading);
+                }
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => {
+                if parse_code_block_info(info).is_rust {
+                    buffer = Buffer::Code(Vec::new());
+                }
+            }
+            Event::Text(text) => match buffer {
+                Buffer::Code(ref mut buf) => buf.push(text.into_string()),
+                Buffer::Heading(ref mut buf) => buf.push_str(&text),
+                Buffer::None => {}
+            },
+            Event::End(TagEnd::CodeBlock) => {
+                if let Buffer::Code(buf) = mem::replace(&mut buffer, Buffer::None) {
+                    if let Some(name) = name.take() {
+                        templates.insert(name, buf.concat());
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    templates
+}
+
+/// Whether `path` is itself a `.skt.md` template file rather than a doc to
+/// extract tests from. Case-insensitive, matching the `case_sensitive: false`
+/// glob option in [`markdown_files_of_directory`].
+fn is_template_file(path: &Path) -> bool {
+    path.to_string_lossy().to_ascii_lowercase().ends_with(".skt.md")
+}
+
+/// The path of the `.skt.md` template file that accompanies a doc, if it were to exist.
+fn template_path_for(doc_path: &Path) -> PathBuf {
+    let mut s = doc_path.as_os_str().to_owned();
+    s.push(".skt.md");
+    s.into()
+}
+
 fn clean_code_line(line: &str) -> Option<&str> {
     let trimmed = line.trim();
 
@@ -256,3 +534,35 @@ fn clean_code_line(line: &str) -> Option<&str> {
     }
     return Some(trimmed);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_template_attr_reads_quoted_name() {
+        assert_eq!(
+            parse_template_attr(r#"rust,template="wrapper""#),
+            Some("wrapper".to_owned())
+        );
+        assert_eq!(parse_template_attr("rust,ignore"), None);
+    }
+
+    #[test]
+    fn skt_md_files_are_recognized_as_templates_not_docs() {
+        // `**/*.md` would otherwise also match `foo.md.skt.md`.
+        assert!(is_template_file(Path::new("foo.md.skt.md")));
+        assert!(!is_template_file(Path::new("foo.md")));
+    }
+
+    #[test]
+    fn is_template_file_is_case_insensitive() {
+        assert!(is_template_file(Path::new("FOO.SKT.MD")));
+    }
+
+    #[test]
+    fn sanitize_identifier_collapses_non_alphanumeric_runs() {
+        assert_eq!(sanitize_identifier("Hello, World!"), "hello_world");
+        assert_eq!(sanitize_identifier("already_valid"), "already_valid");
+    }
+}
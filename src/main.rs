@@ -1,64 +1,10 @@
 use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Parser, Tag, TagEnd};
 use std::collections::HashMap;
-use std::env;
 use std::fs::File;
 use std::io::{self, Error as IoError, Read, Write};
 use std::mem;
 use std::path::{Path, PathBuf};
 
-struct Config {
-    out_dir: PathBuf,
-    root_dir: PathBuf,
-    out_file: PathBuf,
-    target_triple: String,
-    docs: Vec<String>,
-}
-
-fn run(config: &Config) {}
-
-pub fn generate_doc_tests<T: Clone>(docs: &[T])
-where
-    T: AsRef<Path>,
-{
-    if docs.is_empty() {
-        return;
-    }
-
-    let docs = docs
-        .iter()
-        .cloned()
-        .map(|path| path.as_ref().to_str().unwrap().to_owned())
-        .filter(|d| !d.ends_with(".skt.md"))
-        .collect::<Vec<_>>();
-
-    // Inform cargo that it needs to rerun the build script if one of the skeptic files are
-    // modified
-    for doc in &docs {
-        println!("cargo:rerun-if-changed={}", doc);
-
-        let skt = format!("{}.skt.md", doc);
-        if Path::new(&skt).exists() {
-            println!("cargo:rerun-if-changed={}", skt);
-        }
-    }
-
-    let out_dir = env::var("OUT_DIR").unwrap();
-    let cargo_manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-
-    let mut out_file = PathBuf::from(out_dir.clone());
-    out_file.push("skeptic-tests.rs");
-
-    let config = Config {
-        out_dir: PathBuf::from(out_dir),
-        root_dir: PathBuf::from(cargo_manifest_dir),
-        out_file,
-        target_triple: env::var("TARGET").expect("could not get target triple"),
-        docs,
-    };
-
-    run(&config);
-}
-
 pub fn markdown_files_of_directory(dir: &str) -> Vec<PathBuf> {
     use glob::{MatchOptions, glob_with};
 
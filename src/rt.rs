@@ -0,0 +1,130 @@
+//! Runtime support called by the `#[test]` functions that
+//! [`crate::generate_doc_tests`] writes into `OUT_DIR/skeptic-tests.rs`.
+//!
+//! Crates that generate their doc tests at build time depend on this module
+//! staying public and stable, since the generated code calls straight into it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Compiles `text` as a standalone snippet, linking in `crate_name` (the
+/// crate under test), and, unless `no_run` is set, runs it. `compile_flags`
+/// carries a fence's `compile-flags:...` directive, if it had one, straight
+/// through to `rustc`.
+///
+/// Panics (failing the generated `#[test]`) if compilation fails, or if the
+/// resulting binary's exit status doesn't match `should_panic`.
+#[allow(clippy::too_many_arguments)]
+pub fn run_test(
+    out_dir: &str,
+    target_triple: &str,
+    name: &str,
+    text: &str,
+    no_run: bool,
+    should_panic: bool,
+    edition: &str,
+    crate_name: &str,
+    compile_flags: &[&str],
+) {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let test_dir = Path::new(out_dir).join(name);
+    std::fs::create_dir_all(&test_dir).unwrap();
+
+    let src_path = test_dir.join(format!("{}.rs", name));
+    std::fs::write(&src_path, text).unwrap();
+
+    let exe_path = test_dir.join(name);
+    let deps_dir = deps_dir(out_dir);
+
+    let mut cmd = Command::new(&rustc);
+    cmd.arg(&src_path)
+        .arg("-o")
+        .arg(&exe_path)
+        .arg("--target")
+        .arg(target_triple)
+        .arg("--edition")
+        .arg(edition)
+        .arg("-L")
+        .arg(&deps_dir)
+        .args(compile_flags);
+
+    if let Some(rlib) = find_rlib(&deps_dir, crate_name) {
+        cmd.arg("--extern").arg(format!("{}={}", crate_name, rlib.display()));
+    } else {
+        cmd.arg("--extern").arg(crate_name);
+    }
+
+    let status = cmd.status().expect("failed to run rustc");
+
+    if !status.success() {
+        panic!("{} failed to compile", name);
+    }
+
+    if no_run {
+        return;
+    }
+
+    let output = Command::new(&exe_path)
+        .output()
+        .unwrap_or_else(|e| panic!("failed to run compiled snippet {}: {}", name, e));
+
+    if should_panic {
+        assert!(
+            !output.status.success(),
+            "{} was expected to panic but exited successfully",
+            name
+        );
+    } else {
+        assert!(
+            output.status.success(),
+            "{} failed:\nstdout:\n{}\nstderr:\n{}",
+            name,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+/// Derives `target/<profile>/deps` from a build script's `OUT_DIR`
+/// (`target/<profile>/build/<pkg>-<hash>/out`), where cargo places the
+/// already-compiled rlib for the crate under test by the time this runs.
+fn deps_dir(out_dir: &str) -> PathBuf {
+    Path::new(out_dir)
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::parent)
+        .map(|profile_dir| profile_dir.join("deps"))
+        .unwrap_or_else(|| PathBuf::from(out_dir))
+}
+
+/// Finds `lib<crate_name>-*.rlib` in `deps_dir`, preferring the most recently
+/// modified match in case stale rlibs from older builds are still present.
+fn find_rlib(deps_dir: &Path, crate_name: &str) -> Option<PathBuf> {
+    let prefix = format!("lib{}-", crate_name);
+
+    std::fs::read_dir(deps_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "rlib")
+                && path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix))
+        })
+        .max_by_key(|path| path.metadata().and_then(|m| m.modified()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deps_dir_is_derived_from_build_script_out_dir() {
+        assert_eq!(
+            deps_dir("/repo/target/debug/build/mycrate-abcd1234/out"),
+            PathBuf::from("/repo/target/debug/deps")
+        );
+    }
+}
@@ -1,50 +1,160 @@
-use crate::{Config, Test};
+use crate::{Config, Test, load_templates, template_path_for};
+use regex::Regex;
+use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::{copy, create_dir_all, remove_dir_all, write};
+use std::fs::{create_dir_all, remove_dir_all, write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
-fn initialize_test(config: &Config) -> (PathBuf, PathBuf) {
-    let test_dir = &config.test_dir;
-    
-    let src_dir = test_dir.join("src");
-    let main_file = src_dir.join("main.rs");
+type TemplateCache = Mutex<HashMap<PathBuf, HashMap<String, String>>>;
+type IndexedResult = (usize, TestStatus, Vec<LogLine>);
+
+fn test_crate_dir(config: &Config, test: &Test) -> PathBuf {
+    config.test_dir.join(sanitize_dir_name(&test.name()))
+}
+
+fn sanitize_dir_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A `[package] name` unique to `test`, used both as the per-test crate's own
+/// package name and as the final path component of its compiled binary, so
+/// tests sharing `shared_target_dir` never collide on the same output path.
+fn unique_package_name(test: &Test) -> String {
+    format!("skeptic_test_{}", sanitize_dir_name(&test.name()))
+}
+
+/// Materializes a fresh, throwaway crate for `test`: its own `Cargo.toml`
+/// (copied from the crate under test, with `[package] name` rewritten to
+/// `package_name` so concurrent tests don't all build to the same binary
+/// path) and an empty `src/` directory, into which [`run_test`] then writes
+/// the rendered `src/main.rs`.
+fn initialize_test_crate(config: &Config, test: &Test, package_name: &str) -> PathBuf {
+    let crate_dir = test_crate_dir(config, test);
+    let src_dir = crate_dir.join("src");
 
     create_dir_all(&src_dir).unwrap();
 
-    let cargo_toml_src = &config.root_dir.join("Cargo.toml");
-    let cargo_toml_dst = test_dir.join("Cargo.toml");
-    copy(&cargo_toml_src, &cargo_toml_dst).unwrap();
+    let cargo_toml_src = config.root_dir.join("Cargo.toml");
+    let contents = std::fs::read_to_string(&cargo_toml_src).unwrap();
+    write(
+        crate_dir.join("Cargo.toml"),
+        rewrite_package_name(&contents, package_name),
+    )
+    .unwrap();
 
-    (test_dir.to_path_buf(), main_file)
+    crate_dir
 }
 
+/// Rewrites the `name` key of `contents`'s `[package]` section to `new_name`,
+/// leaving every other line untouched.
+fn rewrite_package_name(contents: &str, new_name: &str) -> String {
+    let mut in_package = false;
+    let mut out = String::with_capacity(contents.len());
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_package = trimmed == "[package]";
+        } else if in_package && trimmed.starts_with("name") {
+            out.push_str(&format!("name = \"{}\"\n", new_name));
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Runs every test concurrently across a bounded pool of worker threads, one
+/// isolated crate directory per test sharing a single `CARGO_TARGET_DIR` (so
+/// the dependency graph is built once and reused, not once per worker), then
+/// prints and reports results in the original test order regardless of which
+/// worker finished them.
 pub fn run_tests(config: &Config, tests: Vec<Test>) {
-    let (test_dir, main_file) = initialize_test(config);
+    create_dir_all(&config.test_dir).unwrap();
+    let shared_target_dir = config.test_dir.join("target");
 
-    // let test_results = tests
-    //     .iter()
-    //     .map(|test| run_test(&test_dir, &main_file, test))
-    //     .collect::<Vec<_>>();
+    let total = tests.len();
+    let worker_count = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(total.max(1));
 
+    let (task_tx, task_rx) = mpsc::channel::<(usize, Test)>();
+    let task_rx = Arc::new(Mutex::new(task_rx));
+    let (result_tx, result_rx): (Sender<IndexedResult>, Receiver<IndexedResult>) = mpsc::channel();
+
+    for indexed_test in tests.into_iter().enumerate() {
+        task_tx.send(indexed_test).unwrap();
+    }
+    drop(task_tx);
 
-    let mut results = Vec::with_capacity(tests.len());
+    let template_cache: Arc<TemplateCache> = Arc::new(Mutex::new(HashMap::new()));
+
+    let workers: Vec<_> = (0..worker_count)
+        .map(|_| {
+            let task_rx = Arc::clone(&task_rx);
+            let result_tx = result_tx.clone();
+            let config = config.clone();
+            let template_cache = Arc::clone(&template_cache);
+            let shared_target_dir = shared_target_dir.clone();
+
+            thread::spawn(move || {
+                while let Ok((index, test)) = {
+                    let rx = task_rx.lock().unwrap();
+                    rx.recv()
+                } {
+                    let (status, log) =
+                        run_test(&config, &test, &template_cache, &shared_target_dir);
+                    result_tx.send((index, status, log)).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    let mut results: Vec<Option<(TestStatus, Vec<LogLine>)>> = (0..total).map(|_| None).collect();
+    for (index, status, log) in result_rx {
+        results[index] = Some((status, log));
+    }
 
-    for test in &tests {
-        let status = run_test(&test_dir, &main_file, test);
-        println!("{} {}", test.name, status_print(&status));
-        results.push(status);
+    for worker in workers {
+        worker.join().unwrap();
     }
 
-    if let Err(err) = remove_dir_all(&test_dir) {
+    if let Err(err) = remove_dir_all(&config.test_dir) {
         eprintln!(
             "Warning: Failed to remove test directory {}: {}",
-            test_dir.display(),
+            config.test_dir.display(),
             err
         );
     }
 
-    print_test_stats(&results);
+    // Flush each test's captured output from the main thread, in the
+    // original order, so concurrent workers can't interleave their prints.
+    let mut statuses = Vec::with_capacity(total);
+    for result in results {
+        let (status, log) = result.unwrap();
+        for line in log {
+            line.print();
+        }
+        statuses.push(status);
+    }
+
+    print_test_stats(&statuses);
 }
 
 enum TestStatus {
@@ -53,6 +163,24 @@ enum TestStatus {
     Failed,
 }
 
+/// One line of a test's captured output, tagged by stream. Workers hand these
+/// back to the main thread instead of printing directly, so concurrent tests
+/// can't interleave their output; the main thread flushes them in original
+/// test order once every worker has finished.
+enum LogLine {
+    Out(String),
+    Err(String),
+}
+
+impl LogLine {
+    fn print(&self) {
+        match self {
+            LogLine::Out(s) => println!("{}", s),
+            LogLine::Err(s) => eprintln!("{}", s),
+        }
+    }
+}
+
 fn print_test_stats(results: &Vec<TestStatus>) {
     use ansi_term::Color;
     let mut passed = 0;
@@ -82,55 +210,574 @@ fn status_print(status: &TestStatus) -> impl Display {
     }
 }
 
-fn run_test(test_dir: &Path, main_file: &Path, test: &Test) -> TestStatus {
+fn run_test(
+    config: &Config,
+    test: &Test,
+    template_cache: &TemplateCache,
+    shared_target_dir: &Path,
+) -> (TestStatus, Vec<LogLine>) {
+    let mut log = Vec::new();
+
     if test.ignore {
-        println!("Ignoring test: {}", test.name);
-        return TestStatus::Ignored;
+        log.push(LogLine::Out(format!("Ignoring test: {}", test.name())));
+        log.push(LogLine::Out(format!(
+            "{} {}",
+            test.name(),
+            status_print(&TestStatus::Ignored)
+        )));
+        return (TestStatus::Ignored, log);
     }
 
-    write(main_file, test.text.join("\n")).unwrap();
+    let package_name = unique_package_name(test);
+    let crate_dir = initialize_test_crate(config, test, &package_name);
+    let main_file = crate_dir.join("src").join("main.rs");
+
+    // `None` means "no template requested" (render the snippet normally);
+    // `Some(None)` means a template WAS named but isn't in the `.skt.md` file,
+    // which fails the test rather than silently falling back to the snippet's
+    // normal auto-wrap, since that wouldn't be applying the substitution the
+    // fence explicitly asked for.
+    let template = test.template.as_ref().map(|name| {
+        let skt_path = template_path_for(&test.path);
+        let mut cache = template_cache.lock().unwrap();
+        cache
+            .entry(skt_path.clone())
+            .or_insert_with(|| load_templates(&skt_path))
+            .get(name)
+            .cloned()
+    });
+
+    let crate_name = crate_under_test_name(&config.root_dir.join("Cargo.toml"));
 
-    if test.no_run {
-        println!("Checking (no_run): {}", test.name);
-        let status = Command::new("cargo")
+    let rendered = match template {
+        Some(Some(ref template)) => Some(apply_template(template, &test.text.join("\n"))),
+        Some(None) => None,
+        None => Some(prepare_snippet(test, crate_name.as_deref())),
+    };
+
+    let Some(rendered) = rendered else {
+        log.push(LogLine::Err(format!(
+            "Test '{}' names template \"{}\", but it wasn't found in {}.",
+            test.name(),
+            test.template.as_deref().unwrap_or_default(),
+            template_path_for(&test.path).display()
+        )));
+        if let Err(err) = remove_dir_all(&crate_dir) {
+            log.push(LogLine::Err(format!(
+                "Warning: Failed to remove test crate directory {}: {}",
+                crate_dir.display(),
+                err
+            )));
+        }
+        log.push(LogLine::Out(format!(
+            "{} {}",
+            test.name(),
+            status_print(&TestStatus::Failed)
+        )));
+        return (TestStatus::Failed, log);
+    };
+
+    write(&main_file, rendered).unwrap();
+
+    // A snippet's `edition`/`compile_flags` only apply to the snippet itself,
+    // so they're passed as trailing `cargo rustc -- <flags>` args scoped to
+    // the generated binary, rather than a blanket `RUSTFLAGS` that would also
+    // apply to every dependency in the copied `Cargo.toml`.
+    let extra_flags = extra_rustc_flags_for(test);
+
+    let status = if test.no_run {
+        log.push(LogLine::Out(format!("Checking (no_run): {}", test.name())));
+        let mut cmd = Command::new("cargo");
+        cmd.arg("rustc")
+            .arg("--profile")
             .arg("check")
-            .current_dir(test_dir)
-            .status()
-            .expect("Failed to run cargo check");
+            .current_dir(&crate_dir)
+            .env("CARGO_TARGET_DIR", shared_target_dir);
+        if !extra_flags.is_empty() {
+            cmd.arg("--").args(&extra_flags);
+        }
+        let status = cmd.status().expect("Failed to run cargo rustc");
 
-        if !status.success() {
-            eprintln!("(no_run) test {} failed to compile.", test.name);
-            return TestStatus::Failed;
+        if status.success() {
+            TestStatus::Passed
+        } else {
+            log.push(LogLine::Err(format!(
+                "(no_run) test {} failed to compile.",
+                test.name()
+            )));
+            TestStatus::Failed
+        }
+    } else {
+        let mut cmd = Command::new("cargo");
+        cmd.arg("rustc")
+            .current_dir(&crate_dir)
+            .env("CARGO_TARGET_DIR", shared_target_dir);
+        if !extra_flags.is_empty() {
+            cmd.arg("--").args(&extra_flags);
+        }
+        let build_status = cmd.status().expect("Failed to run cargo rustc");
+
+        if !build_status.success() {
+            log.push(LogLine::Err(format!(
+                "Test '{}' failed to compile.",
+                test.name()
+            )));
+            TestStatus::Failed
+        } else {
+            let exe_path = shared_target_dir.join("debug").join(&package_name);
+            let output = Command::new(&exe_path).output().unwrap_or_else(|e| {
+                panic!("failed to run compiled test {}: {}", test.name(), e)
+            });
+
+            let success = output.status.success();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            if test.should_panic {
+                if success {
+                    log.push(LogLine::Out(format!(
+                        "Test '{}' was expected to panic but passed.",
+                        test.name()
+                    )));
+                    TestStatus::Failed
+                } else {
+                    TestStatus::Passed
+                }
+            } else if !success {
+                log.push(LogLine::Err(format!(
+                    "Test '{}' failed.\nstdout:\n{}\nstderr:\n{}",
+                    test.name(),
+                    stdout,
+                    stderr
+                )));
+                TestStatus::Failed
+            } else {
+                check_expected_output(test, &crate_dir, &stdout, &stderr, &mut log)
+                    .unwrap_or(TestStatus::Passed)
+            }
+        }
+    };
+
+    if let Err(err) = remove_dir_all(&crate_dir) {
+        log.push(LogLine::Err(format!(
+            "Warning: Failed to remove test crate directory {}: {}",
+            crate_dir.display(),
+            err
+        )));
+    }
+
+    log.push(LogLine::Out(format!(
+        "{} {}",
+        test.name(),
+        status_print(&status)
+    )));
+
+    (status, log)
+}
+
+/// Reads the `[package] name` out of a `Cargo.toml`, rewritten with `_` in
+/// place of `-` so it's a valid `extern crate` identifier.
+fn crate_under_test_name(cargo_toml_path: &Path) -> Option<String> {
+    let contents = std::fs::read_to_string(cargo_toml_path).ok()?;
+    let mut in_package = false;
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_package = trimmed == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("name") {
+            let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+            let rest = rest.strip_prefix('"')?;
+            let end = rest.find('"')?;
+            return Some(rest[..end].replace('-', "_"));
         }
-        return TestStatus::Passed;
     }
 
-    let output = Command::new("cargo")
-        .arg("run")
-        .current_dir(test_dir)
-        .output()
-        .expect("Failed to execute test");
+    None
+}
 
-    let success = output.status.success();
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+/// Mirrors rustdoc's handling of bare doctests: if a snippet doesn't already
+/// define `fn main`, hoist its `#![...]`/`extern crate` lines to the top and
+/// wrap the rest (including any `use`, which is legal inside a fn body) in a
+/// `fn main() { ... }`, injecting `#![allow(unused)]` and an `extern crate`
+/// for the crate under test (unless `no_crate_inject` was set on the fence).
+fn prepare_snippet(test: &Test, crate_name: Option<&str>) -> String {
+    if has_fn_main(&test.text) {
+        return test.text.join("\n");
+    }
 
-    if test.should_panic {
-        if success {
-            println!("Test '{}' was expected to panic but passed.", test.name);
-            return TestStatus::Failed;
+    // Inner attributes (`#![...]`) must all come before any other item, so
+    // they're collected separately from `extern crate` rather than hoisted
+    // together. `use` is deliberately left in `body`: unlike these two, it's
+    // legal inside a fn body, and a multi-line `use { ... };` can't be
+    // hoisted line-by-line without tracking brace balance.
+    let mut attrs = Vec::new();
+    let mut imports = Vec::new();
+    let mut body = Vec::new();
+    for line in &test.text {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#![") {
+            attrs.push(line.as_str());
+        } else if trimmed.starts_with("extern crate") {
+            imports.push(line.as_str());
         } else {
-            return TestStatus::Passed;
+            body.push(line.as_str());
         }
-    } else {
-        if !success {
-            eprintln!(
-                "Test '{}' failed.\nstdout:\n{}\nstderr:\n{}",
-                test.name, stdout, stderr
-            );
-            return TestStatus::Failed;
+    }
+
+    let mut out = String::new();
+    out.push_str("#![allow(unused)]\n");
+    for line in attrs {
+        out.push_str(line);
+        out.push('\n');
+    }
+    if !test.no_crate_inject {
+        if let Some(name) = crate_name {
+            out.push_str(&format!("extern crate {};\n", name));
+        }
+    }
+    for line in imports {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("fn main() {\n");
+    for line in body {
+        out.push_str("    ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Whether `text` already defines `fn main`, in which case [`prepare_snippet`]
+/// leaves it alone rather than wrapping it in another one.
+///
+/// Anchored to the start of a line (after whitespace and an optional
+/// `pub`/`async`) rather than a bare substring match, so a comment or string
+/// literal that happens to mention "fn main" doesn't cause a false positive.
+fn has_fn_main(text: &[String]) -> bool {
+    let re = Regex::new(r"(?m)^\s*(pub\s+)?(async\s+)?fn\s+main\s*\(").unwrap();
+    re.is_match(&text.join("\n"))
+}
+
+/// The path of the expectation file for `test`'s captured `stdout`/`stderr`,
+/// a sibling of the doc it came from.
+fn expectation_path(test: &Test, ext: &str) -> PathBuf {
+    let dir = test.path.parent().unwrap_or_else(|| Path::new("."));
+    dir.join(format!("{}.{}", test.name(), ext))
+}
+
+/// A single left-to-right text normalization, applied before diffing captured
+/// output against its expectation so nondeterministic fragments (temp dirs,
+/// addresses) don't cause spurious failures.
+enum Filter {
+    Substring(String, String),
+    Regex(Regex, String),
+}
+
+fn default_filters(crate_dir: &Path) -> Vec<Filter> {
+    vec![
+        Filter::Substring(crate_dir.display().to_string(), "$TEST_DIR".to_owned()),
+        Filter::Regex(Regex::new(r"0x[0-9a-fA-F]+").unwrap(), "$HEX".to_owned()),
+        Filter::Regex(Regex::new(r"(?:/[\w.\-]+)+").unwrap(), "$PATH".to_owned()),
+    ]
+}
+
+fn apply_filters(text: &str, filters: &[Filter]) -> String {
+    filters.iter().fold(text.to_owned(), |acc, filter| match filter {
+        Filter::Substring(from, to) => acc.replace(from.as_str(), to.as_str()),
+        // `NoExpand` treats `to` as a literal string rather than letting the
+        // regex crate interpret `$NAME` in it as a capture-group reference.
+        Filter::Regex(re, to) => re
+            .replace_all(&acc, regex::NoExpand(to.as_str()))
+            .into_owned(),
+    })
+}
+
+/// Compares `stdout`/`stderr` against their `.stdout`/`.stderr` expectation
+/// files, if any exist, after applying [`default_filters`]. Returns
+/// `Some(TestStatus::Failed)` on a mismatch, printing a colored diff; with
+/// `BLESS` set in the environment, mismatches rewrite the expectation file
+/// instead of failing, and a missing expectation file is written from the
+/// captured output rather than skipped.
+fn check_expected_output(
+    test: &Test,
+    crate_dir: &Path,
+    stdout: &str,
+    stderr: &str,
+    log: &mut Vec<LogLine>,
+) -> Option<TestStatus> {
+    let filters = default_filters(crate_dir);
+    let bless = std::env::var_os("BLESS").is_some();
+    let mut failed = false;
+
+    for (ext, actual) in [("stdout", stdout), ("stderr", stderr)] {
+        let path = expectation_path(test, ext);
+        if !path.exists() {
+            if bless {
+                write(&path, actual).unwrap();
+                log.push(LogLine::Out(format!("Blessed (new) {}", path.display())));
+            }
+            continue;
+        }
+
+        let expected = std::fs::read_to_string(&path).unwrap_or_default();
+        let actual_normalized = apply_filters(actual, &filters);
+        let expected_normalized = apply_filters(&expected, &filters);
+
+        if actual_normalized == expected_normalized {
+            continue;
+        }
+
+        if bless {
+            write(&path, actual).unwrap();
+            log.push(LogLine::Out(format!("Blessed {}", path.display())));
+            continue;
+        }
+
+        log.push(LogLine::Err(format!(
+            "Test '{}' {} did not match {}:",
+            test.name(),
+            ext,
+            path.display()
+        )));
+        print_diff(&expected_normalized, &actual_normalized, log);
+        failed = true;
+    }
+
+    failed.then_some(TestStatus::Failed)
+}
+
+/// Renders a minimal colored unified diff of two already-normalized blobs of
+/// text into `log`, rather than printing it directly (see [`LogLine`]).
+fn print_diff(expected: &str, actual: &str, log: &mut Vec<LogLine>) {
+    use ansi_term::Color;
+
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    for i in 0..expected_lines.len().max(actual_lines.len()) {
+        match (expected_lines.get(i), actual_lines.get(i)) {
+            (Some(e), Some(a)) if e == a => log.push(LogLine::Out(format!(" {}", e))),
+            (Some(e), Some(a)) => {
+                log.push(LogLine::Out(Color::Red.paint(format!("-{}", e)).to_string()));
+                log.push(LogLine::Out(Color::Green.paint(format!("+{}", a)).to_string()));
+            }
+            (Some(e), None) => {
+                log.push(LogLine::Out(Color::Red.paint(format!("-{}", e)).to_string()))
+            }
+            (None, Some(a)) => {
+                log.push(LogLine::Out(Color::Green.paint(format!("+{}", a)).to_string()))
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Builds the extra `rustc` arguments carrying a snippet's `edition` override
+/// and `compile_flags`, if it declared any, for passing to
+/// `cargo rustc -- <flags>` so they're scoped to just the snippet's own
+/// binary rather than the whole dependency graph.
+fn extra_rustc_flags_for(test: &Test) -> Vec<String> {
+    let mut flags = Vec::new();
+
+    if let Some(ref edition) = test.edition {
+        flags.push("--edition".to_owned());
+        flags.push(edition.clone());
+    }
+    flags.extend(test.compile_flags.iter().cloned());
+
+    flags
+}
+
+/// Substitutes `snippet` into a template's `{}` (or numbered `{0}`, `{1}`, ...) placeholders.
+fn apply_template(template: &str, snippet: &str) -> String {
+    let mut out = String::with_capacity(template.len() + snippet.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '{' {
+            out.push(c);
+            continue;
+        }
+
+        let mut digits = String::new();
+        let mut closed = false;
+        while let Some(&next) = chars.peek() {
+            if next == '}' {
+                chars.next();
+                closed = true;
+                break;
+            } else if next.is_ascii_digit() {
+                digits.push(next);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if closed {
+            out.push_str(snippet);
         } else {
-            return TestStatus::Passed;
+            out.push('{');
+            out.push_str(&digits);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_template_substitutes_bare_placeholder() {
+        assert_eq!(
+            apply_template("fn main() {\n{}\n}", "let x = 1;"),
+            "fn main() {\nlet x = 1;\n}"
+        );
+    }
+
+    #[test]
+    fn apply_template_substitutes_numbered_placeholders() {
+        assert_eq!(apply_template("{0} and {1}", "x"), "x and x");
+    }
+
+    #[test]
+    fn apply_template_leaves_unrelated_braces_alone() {
+        assert_eq!(apply_template("fn f() {{not a slot}}", "x"), "fn f() {{not a slot}}");
+    }
+
+    fn test_with(edition: Option<&str>, compile_flags: &[&str]) -> Test {
+        Test {
+            text: Vec::new(),
+            path: PathBuf::from("doc.md"),
+            section: None,
+            line_number: 0,
+            ignore: false,
+            no_run: false,
+            should_panic: false,
+            template: None,
+            edition: edition.map(str::to_owned),
+            compile_flags: compile_flags.iter().map(|s| s.to_string()).collect(),
+            no_crate_inject: false,
         }
     }
+
+    #[test]
+    fn extra_rustc_flags_for_includes_edition_and_compile_flags() {
+        let test = test_with(Some("2018"), &["--cfg", "feature=\"foo\""]);
+        assert_eq!(
+            extra_rustc_flags_for(&test),
+            vec!["--edition", "2018", "--cfg", "feature=\"foo\""]
+        );
+    }
+
+    #[test]
+    fn extra_rustc_flags_for_empty_when_unset() {
+        let test = test_with(None, &[]);
+        assert!(extra_rustc_flags_for(&test).is_empty());
+    }
+
+    fn test_with_lines(lines: &[&str]) -> Test {
+        let mut test = test_with(None, &[]);
+        test.text = lines.iter().map(|s| s.to_string()).collect();
+        test
+    }
+
+    #[test]
+    fn has_fn_main_ignores_comments_mentioning_fn_main() {
+        assert!(!has_fn_main(&[
+            "// wrapping bare snippets in fn main is handled for you".to_owned()
+        ]));
+        assert!(has_fn_main(&["fn main() {".to_owned(), "}".to_owned()]));
+        assert!(has_fn_main(&["pub async fn main() {".to_owned(), "}".to_owned()]));
+    }
+
+    #[test]
+    fn prepare_snippet_wraps_bare_snippet_in_fn_main() {
+        let test = test_with_lines(&["let x = 1;"]);
+        let rendered = prepare_snippet(&test, Some("mycrate"));
+        assert!(rendered.contains("fn main() {"));
+        assert!(rendered.contains("    let x = 1;"));
+    }
+
+    #[test]
+    fn prepare_snippet_keeps_inner_attributes_before_any_item() {
+        let test = test_with_lines(&["#![feature(test)]", "let x = 1;"]);
+        let rendered = prepare_snippet(&test, Some("mycrate"));
+
+        let attr_pos = rendered.find("#![feature(test)]").unwrap();
+        let extern_pos = rendered.find("extern crate mycrate;").unwrap();
+        assert!(
+            attr_pos < extern_pos,
+            "inner attributes must precede the injected extern crate:\n{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn prepare_snippet_keeps_a_multi_line_use_intact_inside_main() {
+        let test = test_with_lines(&[
+            "use std::collections::{",
+            "    HashMap,",
+            "    HashSet,",
+            "};",
+            "let x = 1;",
+        ]);
+        let rendered = prepare_snippet(&test, Some("mycrate"));
+        assert_eq!(
+            rendered,
+            "#![allow(unused)]\nextern crate mycrate;\nfn main() {\n    \
+             use std::collections::{\n        HashMap,\n        HashSet,\n    };\n    \
+             let x = 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn prepare_snippet_leaves_snippets_with_fn_main_untouched() {
+        let test = test_with_lines(&["fn main() {", "    println!(\"hi\");", "}"]);
+        assert_eq!(
+            prepare_snippet(&test, Some("mycrate")),
+            "fn main() {\n    println!(\"hi\");\n}"
+        );
+    }
+
+    #[test]
+    fn default_filters_normalize_crate_dir_hex_and_paths() {
+        let crate_dir = Path::new("/tmp/skeptic_test/some_test");
+        let filters = default_filters(crate_dir);
+        let text = format!(
+            "running in {}/target at 0x1a2b3c, see /usr/local/bin/foo",
+            crate_dir.display()
+        );
+
+        let normalized = apply_filters(&text, &filters);
+
+        // The crate dir is substituted first; what's left of `/target` and
+        // `/usr/local/bin/foo` still looks like a path, so it's also caught
+        // by the path regex.
+        assert_eq!(normalized, "running in $TEST_DIR$PATH at $HEX, see $PATH");
+    }
+
+    #[test]
+    fn apply_filters_leaves_unrelated_text_alone() {
+        assert_eq!(apply_filters("hello world", &[]), "hello world");
+    }
+
+    #[test]
+    fn rewrite_package_name_replaces_only_the_package_section_name() {
+        let cargo_toml = "[package]\nname = \"mycrate\"\nversion = \"0.1.0\"\n\n[dependencies]\nname = \"not-this-one\"\n";
+        let rewritten = rewrite_package_name(cargo_toml, "skeptic_test_foo");
+        assert!(rewritten.contains("name = \"skeptic_test_foo\"\n"));
+        assert!(rewritten.contains("name = \"not-this-one\""));
+    }
 }